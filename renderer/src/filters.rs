@@ -0,0 +1,529 @@
+use encase::{ShaderType, UniformBuffer};
+
+/// A post-processing pass that samples one texture and writes into another,
+/// inspired by ruffle's `Filter` pipeline. Each filter owns its own
+/// `RenderPipeline` and bind group layout (sampler + input texture, plus an
+/// optional per-filter uniform) so the chain can be reordered or extended
+/// without filters knowing about each other.
+pub trait Filter {
+    fn name(&self) -> &'static str;
+    fn enabled(&mut self) -> &mut bool;
+
+    fn apply(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        input_view: &wgpu::TextureView,
+        output_view: &wgpu::TextureView,
+        input_size: (u32, u32),
+    );
+
+    /// Draws this filter's parameter sliders (if any) into the egui panel.
+    /// The enabled checkbox itself is drawn by the caller, uniformly for
+    /// every filter in the chain.
+    fn ui(&mut self, _ui: &mut egui::Ui, _queue: &wgpu::Queue) {}
+}
+
+fn create_sampler(device: &wgpu::Device) -> wgpu::Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Filter Sampler"),
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    })
+}
+
+fn create_fullscreen_pipeline(
+    device: &wgpu::Device,
+    label: &str,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(&format!("{label} Pipeline Layout")),
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            compilation_options: Default::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+fn run_fullscreen_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &str,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+    output_view: &wgpu::TextureView,
+) {
+    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: output_view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+    rpass.set_pipeline(pipeline);
+    rpass.set_bind_group(0, bind_group, &[]);
+    rpass.draw(0..3, 0..1);
+}
+
+/// Always-run final pass that samples the end of the filter chain and
+/// writes it into the swapchain (or the screenshot texture), since
+/// post-processing never renders directly to the surface.
+pub struct Blit {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+}
+
+impl Blit {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/blit.wgsl"));
+        let bind_group_layout = texture_and_sampler_bind_group_layout(device, "Blit", None);
+        let pipeline = create_fullscreen_pipeline(device, "Blit Pipeline", &shader, format, &bind_group_layout);
+        let sampler = create_sampler(device);
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+        }
+    }
+
+    pub fn apply(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        input_view: &wgpu::TextureView,
+        output_view: &wgpu::TextureView,
+    ) {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Blit Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(input_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        run_fullscreen_pass(encoder, "Blit Pass", &self.pipeline, &bind_group, output_view);
+    }
+}
+
+/// Builds the `texture_2d` + `sampler` bind group layout shared by every
+/// filter, with an optional trailing uniform entry for filters that have
+/// parameters.
+fn texture_and_sampler_bind_group_layout(
+    device: &wgpu::Device,
+    label: &str,
+    uniform_size: Option<wgpu::BufferSize>,
+) -> wgpu::BindGroupLayout {
+    let mut entries = vec![
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        },
+    ];
+
+    if let Some(min_binding_size) = uniform_size {
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: Some(min_binding_size),
+            },
+            count: None,
+        });
+    }
+
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some(&format!("{label} Bind Group Layout")),
+        entries: &entries,
+    })
+}
+
+fn write_uniform<T: ShaderType + encase::internal::WriteInto>(
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    value: &T,
+) {
+    let mut encase_buffer = UniformBuffer::new(Vec::new());
+    encase_buffer.write(value).unwrap();
+    queue.write_buffer(buffer, 0, encase_buffer.as_ref());
+}
+
+pub struct Grayscale {
+    enabled: bool,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+}
+
+impl Grayscale {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/grayscale.wgsl"));
+        let bind_group_layout = texture_and_sampler_bind_group_layout(device, "Grayscale", None);
+        let pipeline =
+            create_fullscreen_pipeline(device, "Grayscale Pipeline", &shader, format, &bind_group_layout);
+
+        Self {
+            enabled: false,
+            pipeline,
+            bind_group_layout,
+            sampler: create_sampler(device),
+        }
+    }
+}
+
+impl Filter for Grayscale {
+    fn name(&self) -> &'static str {
+        "Grayscale"
+    }
+
+    fn enabled(&mut self) -> &mut bool {
+        &mut self.enabled
+    }
+
+    fn apply(
+        &self,
+        device: &wgpu::Device,
+        _queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        input_view: &wgpu::TextureView,
+        output_view: &wgpu::TextureView,
+        _input_size: (u32, u32),
+    ) {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Grayscale Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(input_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        run_fullscreen_pass(encoder, "Grayscale Pass", &self.pipeline, &bind_group, output_view);
+    }
+}
+
+#[derive(ShaderType)]
+struct BlurParams {
+    texel_size_x: f32,
+    texel_size_y: f32,
+    radius: f32,
+}
+
+pub struct GaussianBlur {
+    enabled: bool,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    radius: f32,
+}
+
+impl GaussianBlur {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/gaussian_blur.wgsl"));
+        let bind_group_layout = texture_and_sampler_bind_group_layout(
+            device,
+            "Gaussian Blur",
+            wgpu::BufferSize::new(BlurParams::min_size().get()),
+        );
+        let pipeline = create_fullscreen_pipeline(
+            device,
+            "Gaussian Blur Pipeline",
+            &shader,
+            format,
+            &bind_group_layout,
+        );
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Gaussian Blur Uniform Buffer"),
+            size: BlurParams::min_size().get(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let radius = 2.0;
+        // `texel_size` depends on the input texture's actual size, which
+        // isn't known until `apply`; write a placeholder here and let the
+        // first `apply` call replace it with the real value.
+        write_uniform(
+            queue,
+            &uniform_buffer,
+            &BlurParams {
+                texel_size_x: 0.0,
+                texel_size_y: 0.0,
+                radius,
+            },
+        );
+
+        Self {
+            enabled: false,
+            pipeline,
+            bind_group_layout,
+            sampler: create_sampler(device),
+            uniform_buffer,
+            radius,
+        }
+    }
+}
+
+impl Filter for GaussianBlur {
+    fn name(&self) -> &'static str {
+        "Gaussian Blur"
+    }
+
+    fn enabled(&mut self) -> &mut bool {
+        &mut self.enabled
+    }
+
+    fn apply(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        input_view: &wgpu::TextureView,
+        output_view: &wgpu::TextureView,
+        input_size: (u32, u32),
+    ) {
+        write_uniform(
+            queue,
+            &self.uniform_buffer,
+            &BlurParams {
+                texel_size_x: 1.0 / input_size.0 as f32,
+                texel_size_y: 1.0 / input_size.1 as f32,
+                radius: self.radius,
+            },
+        );
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Gaussian Blur Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(input_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        run_fullscreen_pass(
+            encoder,
+            "Gaussian Blur Pass",
+            &self.pipeline,
+            &bind_group,
+            output_view,
+        );
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, _queue: &wgpu::Queue) {
+        // `texel_size` depends on the input texture's size, so the uniform
+        // is rewritten on the next `apply` rather than here.
+        ui.add(egui::Slider::new(&mut self.radius, 1.0..=8.0).text("Radius"));
+    }
+}
+
+#[derive(ShaderType)]
+struct ColorAdjustParams {
+    brightness: f32,
+    contrast: f32,
+    saturation: f32,
+}
+
+pub struct ColorAdjust {
+    enabled: bool,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    brightness: f32,
+    contrast: f32,
+    saturation: f32,
+}
+
+impl ColorAdjust {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/color_adjust.wgsl"));
+        let bind_group_layout = texture_and_sampler_bind_group_layout(
+            device,
+            "Color Adjust",
+            wgpu::BufferSize::new(ColorAdjustParams::min_size().get()),
+        );
+        let pipeline = create_fullscreen_pipeline(
+            device,
+            "Color Adjust Pipeline",
+            &shader,
+            format,
+            &bind_group_layout,
+        );
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Color Adjust Uniform Buffer"),
+            size: ColorAdjustParams::min_size().get(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let (brightness, contrast, saturation) = (0.0, 1.0, 1.0);
+        write_uniform(
+            queue,
+            &uniform_buffer,
+            &ColorAdjustParams {
+                brightness,
+                contrast,
+                saturation,
+            },
+        );
+
+        Self {
+            enabled: false,
+            pipeline,
+            bind_group_layout,
+            sampler: create_sampler(device),
+            uniform_buffer,
+            brightness,
+            contrast,
+            saturation,
+        }
+    }
+}
+
+impl Filter for ColorAdjust {
+    fn name(&self) -> &'static str {
+        "Color Adjust"
+    }
+
+    fn enabled(&mut self) -> &mut bool {
+        &mut self.enabled
+    }
+
+    fn apply(
+        &self,
+        device: &wgpu::Device,
+        _queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        input_view: &wgpu::TextureView,
+        output_view: &wgpu::TextureView,
+        _input_size: (u32, u32),
+    ) {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Color Adjust Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(input_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        run_fullscreen_pass(
+            encoder,
+            "Color Adjust Pass",
+            &self.pipeline,
+            &bind_group,
+            output_view,
+        );
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, queue: &wgpu::Queue) {
+        let mut changed = false;
+        changed |= ui
+            .add(egui::Slider::new(&mut self.brightness, -1.0..=1.0).text("Brightness"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.contrast, 0.0..=2.0).text("Contrast"))
+            .changed();
+        changed |= ui
+            .add(egui::Slider::new(&mut self.saturation, 0.0..=2.0).text("Saturation"))
+            .changed();
+
+        if changed {
+            write_uniform(
+                queue,
+                &self.uniform_buffer,
+                &ColorAdjustParams {
+                    brightness: self.brightness,
+                    contrast: self.contrast,
+                    saturation: self.saturation,
+                },
+            );
+        }
+    }
+}
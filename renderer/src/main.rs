@@ -1,8 +1,16 @@
-use encase::{ShaderType, UniformBuffer};
+mod filters;
+mod uniform_pool;
+
+use std::sync::Arc;
+
+use encase::ShaderType;
+use filters::Filter;
+use uniform_pool::UniformBufferPool;
 use winit::{
+    dpi::PhysicalSize,
     event::{Event, WindowEvent},
-    event_loop::EventLoop,
-    window::Window,
+    event_loop::{EventLoop, EventLoopWindowTarget},
+    window::{Window, WindowBuilder},
 };
 
 #[derive(ShaderType)]
@@ -24,24 +32,117 @@ impl RGBA {
     }
 }
 
-fn update_color_buffer(queue: &wgpu::Queue, wgpu_buffer: &wgpu::Buffer, color: &RGBA) {
-    let mut encase_buffer = UniformBuffer::new(Vec::new());
-    encase_buffer.write(color).unwrap();
-    queue.write_buffer(&wgpu_buffer, 0, encase_buffer.as_ref());
+/// Used as the scene's clear color so the area around the drawn triangle
+/// matches the egui color picker, including under a transparent macOS title
+/// bar where the clear color is the only thing visible.
+fn clear_color(color_uniform: [f32; 4]) -> wgpu::Color {
+    wgpu::Color {
+        r: color_uniform[0] as f64,
+        g: color_uniform[1] as f64,
+        b: color_uniform[2] as f64,
+        a: color_uniform[3] as f64,
+    }
 }
 
-async fn initialize_wgpu<'window>(
-    window: &'window Window,
+/// 4x MSAA, matching what most "real" renderers turn on by default. This and
+/// `DEPTH_FORMAT` are compile-time settings, not something a user can toggle
+/// at runtime — change the constant and rebuild to try a different value.
+/// Both apply to every pass built from `render_pipeline` (the main render
+/// pass and the screenshot pass alike), since a pipeline's multisample and
+/// depth-stencil state is fixed at pipeline-creation time and every pass
+/// using it must provide matching attachments.
+const SAMPLE_COUNT: u32 = 4;
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Creates the multisampled color attachment that the render pass draws
+/// into before resolving down to the single-sample `scene_color` texture.
+fn create_multisampled_framebuffer(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+) -> wgpu::TextureView {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Multisampled Framebuffer"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: SAMPLE_COUNT,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+fn create_depth_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::TextureView {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Depth Texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: SAMPLE_COUNT,
+        dimension: wgpu::TextureDimension::D2,
+        format: DEPTH_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        view_formats: &[],
+    });
+
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// Creates a single-sample, sampleable color texture at `format`, used for
+/// the scene's MSAA resolve target and for each stage of the filter chain.
+fn create_color_texture(
+    device: &wgpu::Device,
+    label: &str,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+) -> wgpu::TextureView {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+async fn initialize_wgpu(
+    window: Arc<Window>,
     window_size: &winit::dpi::PhysicalSize<u32>,
 ) -> (
     wgpu::Instance,
-    wgpu::Surface<'window>,
+    wgpu::Surface<'static>,
     wgpu::Adapter,
     wgpu::Device,
     wgpu::Queue,
     wgpu::SurfaceConfiguration,
 ) {
     let instance = wgpu::Instance::default();
+    // Taking ownership of an `Arc<Window>` rather than borrowing gives a
+    // `Surface<'static>` that keeps its window alive and can be stored in
+    // `State`, then dropped and recreated on its own, which matters on
+    // Android where the native window is destroyed and recreated around
+    // suspend/resume.
     let surface = instance.create_surface(window).unwrap();
     let adapter = instance
         .request_adapter(&wgpu::RequestAdapterOptions {
@@ -76,63 +177,38 @@ async fn initialize_wgpu<'window>(
     (instance, surface, adapter, device, queue, surface_config)
 }
 
+/// Creates and configures a surface against a window, used by
+/// `State::resume` to recreate the surface around a new `Window` after an
+/// Android-style suspend. `initialize_wgpu` creates its first surface
+/// itself, since it needs that surface to pick an adapter.
+fn create_surface(
+    instance: &wgpu::Instance,
+    adapter: &wgpu::Adapter,
+    device: &wgpu::Device,
+    window: Arc<Window>,
+    size: &PhysicalSize<u32>,
+) -> wgpu::Surface<'static> {
+    let surface = instance.create_surface(window).unwrap();
+    let surface_config = surface
+        .get_default_config(adapter, size.width, size.height)
+        .expect("Failed to get default surface configuration");
+    surface.configure(device, &surface_config);
+
+    surface
+}
+
 fn initialize_shader(
     device: &wgpu::Device,
-    queue: &wgpu::Queue,
     surface: &wgpu::Surface,
     adapter: &wgpu::Adapter,
-) -> (
-    wgpu::ShaderModule,
-    [f32; 4],
-    wgpu::Buffer,
-    wgpu::BindGroup,
-    wgpu::PipelineLayout,
-    wgpu::RenderPipeline,
-) {
+    color_bind_group_layout: &wgpu::BindGroupLayout,
+) -> (wgpu::ShaderModule, wgpu::PipelineLayout, wgpu::RenderPipeline) {
     // Load the shaders from disk
     let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/shader.wgsl"));
 
-    let color_uniform = [1.0, 0.0, 0.0, 1.0];
-    let color_uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-        label: Some("Color Uniform Buffer"),
-        size: std::mem::size_of::<RGBA>() as u64,
-        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        mapped_at_creation: false,
-    });
-    // Update the color buffer with the initial color
-    update_color_buffer(queue, &color_uniform_buffer, &RGBA::new(color_uniform));
-
-    let color_bind_group_layout =
-        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: Some("Color Bind Group Layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
-        });
-
-    let color_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: Some("Color Bind Group"),
-        layout: &color_bind_group_layout,
-        entries: &[wgpu::BindGroupEntry {
-            binding: 0,
-            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                buffer: &color_uniform_buffer,
-                offset: 0,
-                size: None,
-            }),
-        }],
-    });
-
     let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: Some("Pipeline Layout"),
-        bind_group_layouts: &[&color_bind_group_layout],
+        bind_group_layouts: &[color_bind_group_layout],
         push_constant_ranges: &[],
     });
 
@@ -159,19 +235,21 @@ fn initialize_shader(
             })],
         }),
         primitive: wgpu::PrimitiveState::default(),
-        depth_stencil: None,
-        multisample: wgpu::MultisampleState::default(),
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: SAMPLE_COUNT,
+            ..Default::default()
+        },
         multiview: None,
     });
 
-    (
-        shader,
-        color_uniform,
-        color_uniform_buffer,
-        color_bind_group,
-        pipeline_layout,
-        render_pipeline,
-    )
+    (shader, pipeline_layout, render_pipeline)
 }
 
 fn initialize_egui(
@@ -180,6 +258,9 @@ fn initialize_egui(
     surface_config: &wgpu::SurfaceConfiguration,
     pixels_per_point: f32,
 ) -> (egui_wgpu::Renderer, egui_winit::State) {
+    // egui is drawn in its own pass directly onto the swapchain, after the
+    // scene and filter chain have resolved into it, so it doesn't need MSAA
+    // or a depth buffer of its own.
     let egui_renderer = egui_wgpu::Renderer::new(&device, surface_config.format, None, 1);
     let egui_ctx = egui::Context::default();
 
@@ -195,159 +276,549 @@ fn initialize_egui(
     (egui_renderer, egui_state)
 }
 
-async fn run(event_loop: EventLoop<()>, window: Window) {
-    let mut window_size = window.inner_size();
-    window_size.width = window_size.width.max(1);
-    window_size.height = window_size.height.max(1);
-    let pixels_per_point = window.scale_factor() as f32;
+/// Owns every GPU and UI resource needed to draw a frame, and knows how to
+/// recover the surface when it is lost or goes out of date instead of
+/// panicking.
+///
+/// `window` and `surface` are `Option`s because Android destroys the native
+/// window (and with it the surface) when the app is backgrounded: `suspend`
+/// drops both, and `resume` recreates them against a fresh `Window`, while
+/// the device, adapter and pipelines created up front in `new` stay alive
+/// the whole time, since only the surface and the window it was created
+/// from are actually invalidated.
+struct State {
+    window: Option<Arc<Window>>,
+    // The shader module and pipeline layout aren't used after setup, but
+    // must stay alive for as long as the resources that were created from
+    // them. `instance` and `_adapter` are kept because `resume` needs them
+    // to recreate the surface.
+    instance: wgpu::Instance,
+    _adapter: wgpu::Adapter,
+    _shader: wgpu::ShaderModule,
+    _pipeline_layout: wgpu::PipelineLayout,
+
+    surface: Option<wgpu::Surface<'static>>,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    surface_config: wgpu::SurfaceConfiguration,
+    size: PhysicalSize<u32>,
+
+    color_uniform: [f32; 4],
+    color_uniform_pool: UniformBufferPool<RGBA>,
+    color_uniform_offset: wgpu::DynamicOffset,
+    render_pipeline: wgpu::RenderPipeline,
+    multisampled_framebuffer: wgpu::TextureView,
+    depth_texture_view: wgpu::TextureView,
+
+    // The scene resolves into `scene_color`, which then feeds the filter
+    // chain; `ping`/`pong` let each enabled filter sample the previous
+    // stage's output while writing into a texture that isn't also bound as
+    // an input, without every filter needing its own pair of textures.
+    scene_color: wgpu::TextureView,
+    ping: wgpu::TextureView,
+    pong: wgpu::TextureView,
+    blit: filters::Blit,
+    filter_chain: Vec<Box<dyn Filter>>,
 
-    let (instance, surface, adapter, device, queue, mut surface_config) =
-        initialize_wgpu(&window, &window_size).await;
+    pixels_per_point: f32,
+    egui_renderer: egui_wgpu::Renderer,
+    egui_state: egui_winit::State,
+}
 
-    let (
-        shader,
-        mut color_uniform,
-        color_uniform_buffer,
-        color_bind_group,
-        pipeline_layout,
-        render_pipeline,
-    ) = initialize_shader(&device, &queue, &surface, &adapter);
+impl State {
+    async fn new(window: Arc<Window>) -> Self {
+        let mut size = window.inner_size();
+        size.width = size.width.max(1);
+        size.height = size.height.max(1);
+        let pixels_per_point = window.scale_factor() as f32;
+
+        let (instance, surface, adapter, device, queue, surface_config) =
+            initialize_wgpu(window.clone(), &size).await;
+
+        let mut color_uniform_pool =
+            UniformBufferPool::<RGBA>::new(&device, "Color Uniform", wgpu::ShaderStages::FRAGMENT);
+        let color_uniform = [1.0, 0.0, 0.0, 1.0];
+        let color_uniform_offset =
+            color_uniform_pool.set(&device, &queue, 0, &RGBA::new(color_uniform));
+
+        let (shader, pipeline_layout, render_pipeline) = initialize_shader(
+            &device,
+            &surface,
+            &adapter,
+            color_uniform_pool.bind_group_layout(),
+        );
+
+        let (egui_renderer, egui_state) =
+            initialize_egui(&window, &device, &surface_config, pixels_per_point);
+
+        let multisampled_framebuffer =
+            create_multisampled_framebuffer(&device, surface_config.format, size.width, size.height);
+        let depth_texture_view = create_depth_texture(&device, size.width, size.height);
+
+        let scene_color =
+            create_color_texture(&device, "Scene Color", surface_config.format, size.width, size.height);
+        let ping = create_color_texture(&device, "Filter Ping", surface_config.format, size.width, size.height);
+        let pong = create_color_texture(&device, "Filter Pong", surface_config.format, size.width, size.height);
+        let blit = filters::Blit::new(&device, surface_config.format);
+        let filter_chain: Vec<Box<dyn Filter>> = vec![
+            Box::new(filters::Grayscale::new(&device, surface_config.format)),
+            Box::new(filters::GaussianBlur::new(&device, &queue, surface_config.format)),
+            Box::new(filters::ColorAdjust::new(&device, &queue, surface_config.format)),
+        ];
 
-    let (mut egui_renderer, mut egui_state) =
-        initialize_egui(&window, &device, &surface_config, pixels_per_point);
+        Self {
+            window: Some(window),
+            instance,
+            _adapter: adapter,
+            _shader: shader,
+            _pipeline_layout: pipeline_layout,
+
+            surface: Some(surface),
+            device,
+            queue,
+            surface_config,
+            size,
+
+            color_uniform,
+            color_uniform_pool,
+            color_uniform_offset,
+            render_pipeline,
+            multisampled_framebuffer,
+            depth_texture_view,
+
+            scene_color,
+            ping,
+            pong,
+            blit,
+            filter_chain,
+
+            pixels_per_point,
+            egui_renderer,
+            egui_state,
+        }
+    }
 
-    let window = &window;
+    /// Recreates the surface against a (possibly new) `Window`, reusing the
+    /// existing instance, adapter and device. Called on every `Resumed`
+    /// after the first: on Android the native window backing the old
+    /// surface no longer exists, so neither does the surface, but there's no
+    /// need to renegotiate an adapter/device to replace it. Also runs the
+    /// same texture-recreation path as `resize()`, since the window can come
+    /// back at a different size than it went away at (e.g. a rotation while
+    /// backgrounded) and the multisampled/depth/filter-chain textures must
+    /// match the new surface size exactly.
+    fn resume(&mut self, window: Arc<Window>) {
+        let mut size = window.inner_size();
+        size.width = size.width.max(1);
+        size.height = size.height.max(1);
+
+        let surface = create_surface(
+            &self.instance,
+            &self._adapter,
+            &self.device,
+            window.clone(),
+            &size,
+        );
+
+        self.window = Some(window);
+        self.surface = Some(surface);
+        self.resize(size);
+    }
 
-    event_loop
-        .run(move |event, target| {
-            // Have the closure take ownership of the resources.
-            // `event_loop.run` never returns, therefore we must do this to ensure
-            // the resources are properly cleaned up.
-            let _ = (&instance, &adapter, &shader, &pipeline_layout);
+    /// Drops the surface and window without touching the device, adapter or
+    /// any pipeline, so `resume` can cheaply pick back up once a new window
+    /// is available.
+    fn suspend(&mut self) {
+        self.surface = None;
+        self.window = None;
+    }
 
-            if let Event::WindowEvent {
-                window_id: _,
-                event: window_event,
-            } = event
-            {
-                let egui_event_response = egui_state.on_window_event(window, &window_event);
+    fn resize(&mut self, new_size: PhysicalSize<u32>) {
+        if new_size.width == 0 || new_size.height == 0 {
+            return;
+        }
 
-                if egui_event_response.repaint {
-                    window.request_redraw();
-                }
+        self.size = new_size;
+        self.surface_config.width = new_size.width;
+        self.surface_config.height = new_size.height;
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.surface_config);
+        }
 
-                if egui_event_response.consumed {
-                    return;
-                }
+        self.multisampled_framebuffer = create_multisampled_framebuffer(
+            &self.device,
+            self.surface_config.format,
+            new_size.width,
+            new_size.height,
+        );
+        self.depth_texture_view = create_depth_texture(&self.device, new_size.width, new_size.height);
+
+        self.scene_color = create_color_texture(
+            &self.device,
+            "Scene Color",
+            self.surface_config.format,
+            new_size.width,
+            new_size.height,
+        );
+        self.ping = create_color_texture(
+            &self.device,
+            "Filter Ping",
+            self.surface_config.format,
+            new_size.width,
+            new_size.height,
+        );
+        self.pong = create_color_texture(
+            &self.device,
+            "Filter Pong",
+            self.surface_config.format,
+            new_size.width,
+            new_size.height,
+        );
+    }
 
-                match window_event {
-                    WindowEvent::Resized(new_size) => {
-                        // Reconfigure the surface with the new size
-                        surface_config.width = new_size.width.max(1);
-                        surface_config.height = new_size.height.max(1);
-                        surface.configure(&device, &surface_config);
-                        // On macos the window needs to be redrawn manually after resizing
-                        window.request_redraw();
-                    }
+    /// Feeds a window event to egui and returns whether egui consumed it, so
+    /// the caller knows whether to keep handling it itself. No-op while
+    /// suspended, since there's no window to feed events to or redraw.
+    fn input(&mut self, event: &WindowEvent) -> bool {
+        let Some(window) = &self.window else {
+            return false;
+        };
 
-                    WindowEvent::RedrawRequested => {
-                        let frame = surface
-                            .get_current_texture()
-                            .expect("Failed to acquire next swap chain texture");
-                        let view = frame
-                            .texture
-                            .create_view(&wgpu::TextureViewDescriptor::default());
-                        let mut encoder =
-                            device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                                label: Some("Command Encoder"),
-                            });
-
-                        let egui_raw_input = egui_state.take_egui_input(window);
-                        let egui_full_output = egui_state.egui_ctx().run(
-                            egui_raw_input,
-                            |egui_ctx: &egui::Context| {
-                                egui::CentralPanel::default()
-                                    .frame(
-                                        egui::Frame::none().inner_margin(egui::Margin::same(10.0)),
-                                    )
-                                    .show(egui_ctx, |ui| {
-                                        if ui
-                                            .color_edit_button_rgba_unmultiplied(&mut color_uniform)
-                                            .changed()
-                                        {
-                                            update_color_buffer(
-                                                &queue,
-                                                &color_uniform_buffer,
-                                                &RGBA::new(color_uniform),
-                                            );
-                                        }
-                                    });
-                            },
-                        );
-                        let egui_primitives = egui_state
-                            .egui_ctx()
-                            .tessellate(egui_full_output.shapes, egui_full_output.pixels_per_point);
-                        let egui_screen_descriptor = egui_wgpu::ScreenDescriptor {
-                            size_in_pixels: [surface_config.width, surface_config.height],
-                            pixels_per_point: pixels_per_point,
-                        };
-
-                        for (id, image_delta) in egui_full_output.textures_delta.set {
-                            egui_renderer.update_texture(&device, &queue, id, &image_delta);
-                        }
+        let egui_event_response = self.egui_state.on_window_event(window, event);
 
-                        egui_renderer.update_buffers(
-                            &device,
-                            &queue,
-                            &mut encoder,
-                            &egui_primitives,
-                            &egui_screen_descriptor,
-                        );
-
-                        {
-                            let mut rpass =
-                                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                                    label: Some("Render Pass"),
-                                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                                        view: &view,
-                                        resolve_target: None,
-                                        ops: wgpu::Operations {
-                                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                                            store: wgpu::StoreOp::Store,
-                                        },
-                                    })],
-                                    depth_stencil_attachment: None,
-                                    timestamp_writes: None,
-                                    occlusion_query_set: None,
-                                });
-                            rpass.set_pipeline(&render_pipeline);
-
-                            rpass.set_bind_group(0, &color_bind_group, &[]);
-
-                            rpass.draw(0..3, 0..1);
-
-                            egui_renderer.render(
-                                &mut rpass,
-                                &egui_primitives,
-                                &egui_screen_descriptor,
-                            );
-                        }
+        if egui_event_response.repaint {
+            window.request_redraw();
+        }
 
-                        queue.submit(Some(encoder.finish()));
-                        frame.present();
+        egui_event_response.consumed
+    }
 
-                        for id in egui_full_output.textures_delta.free {
-                            egui_renderer.free_texture(&id);
-                        }
-                    }
+    /// Runs the enabled filters, in order, ping-ponging between `ping` and
+    /// `pong` so each filter only ever reads a texture it isn't also
+    /// writing into this pass, starting from `scene_color`, then blits
+    /// wherever the chain ended up (`scene_color` itself if no filter is
+    /// enabled) into `output_view`. Shared by `render()`
+    /// and `capture_screenshot()` so a screenshot taken with filters enabled
+    /// goes through exactly the same chain as what's on screen.
+    fn run_filter_chain(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        input_size: (u32, u32),
+        output_view: &wgpu::TextureView,
+    ) {
+        let mut current_input = &self.scene_color;
+        let mut ping_is_next = true;
+        for filter in self.filter_chain.iter_mut() {
+            if !*filter.enabled() {
+                continue;
+            }
 
-                    WindowEvent::CloseRequested => target.exit(),
+            let output = if ping_is_next { &self.ping } else { &self.pong };
+            filter.apply(&self.device, &self.queue, encoder, current_input, output, input_size);
+            current_input = output;
+            ping_is_next = !ping_is_next;
+        }
+        self.blit.apply(&self.device, encoder, current_input, output_view);
+    }
 
-                    _ => {}
-                };
+    /// Renders a frame, or does nothing if the surface is currently gone
+    /// (suspended), since there's nothing to present to.
+    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        let (Some(surface), Some(window)) = (&self.surface, self.window.clone()) else {
+            return Ok(());
+        };
+        let frame = surface.get_current_texture()?;
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Command Encoder"),
+            });
+
+        let egui_raw_input = self.egui_state.take_egui_input(&window);
+        let color_uniform = &mut self.color_uniform;
+        let device = &self.device;
+        let queue = &self.queue;
+        let color_uniform_pool = &mut self.color_uniform_pool;
+        let color_uniform_offset = &mut self.color_uniform_offset;
+        let filter_chain = &mut self.filter_chain;
+        // There's no real filesystem to write `screenshot.png` to in a
+        // browser, so the capture path (button included) only exists on
+        // native builds.
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut screenshot_requested = false;
+        let egui_full_output =
+            self.egui_state
+                .egui_ctx()
+                .run(egui_raw_input, |egui_ctx: &egui::Context| {
+                    egui::CentralPanel::default()
+                        .frame(egui::Frame::none().inner_margin(egui::Margin::same(10.0)))
+                        .show(egui_ctx, |ui| {
+                            if ui
+                                .color_edit_button_rgba_unmultiplied(color_uniform)
+                                .changed()
+                            {
+                                *color_uniform_offset =
+                                    color_uniform_pool.set(device, queue, 0, &RGBA::new(*color_uniform));
+                            }
+
+                            #[cfg(not(target_arch = "wasm32"))]
+                            if ui.button("Save screenshot").clicked() {
+                                screenshot_requested = true;
+                            }
+
+                            ui.separator();
+                            ui.label("Filters");
+                            for filter in filter_chain.iter_mut() {
+                                let name = filter.name();
+                                ui.checkbox(filter.enabled(), name);
+                                if *filter.enabled() {
+                                    filter.ui(ui, queue);
+                                }
+                            }
+                        });
+                });
+        let egui_primitives = self
+            .egui_state
+            .egui_ctx()
+            .tessellate(egui_full_output.shapes, egui_full_output.pixels_per_point);
+        let egui_screen_descriptor = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [self.surface_config.width, self.surface_config.height],
+            pixels_per_point: self.pixels_per_point,
+        };
+
+        for (id, image_delta) in &egui_full_output.textures_delta.set {
+            self.egui_renderer
+                .update_texture(&self.device, &self.queue, *id, image_delta);
+        }
+
+        self.egui_renderer.update_buffers(
+            &self.device,
+            &self.queue,
+            &mut encoder,
+            &egui_primitives,
+            &egui_screen_descriptor,
+        );
+
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.multisampled_framebuffer,
+                    resolve_target: Some(&self.scene_color),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(clear_color(self.color_uniform)),
+                        store: wgpu::StoreOp::Discard,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            rpass.set_pipeline(&self.render_pipeline);
+
+            rpass.set_bind_group(
+                0,
+                self.color_uniform_pool.bind_group(),
+                &[self.color_uniform_offset],
+            );
+
+            rpass.draw(0..3, 0..1);
+        }
+
+        let input_size = (self.surface_config.width, self.surface_config.height);
+
+        // Composite the end of the filter chain into the swapchain, then
+        // draw egui in its own pass on top, directly on the swapchain, so
+        // egui is never affected by the post-processing chain.
+        self.run_filter_chain(&mut encoder, input_size, &view);
+
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Egui Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            self.egui_renderer
+                .render(&mut rpass, &egui_primitives, &egui_screen_descriptor);
+        }
+
+        self.queue.submit(Some(encoder.finish()));
+        frame.present();
+
+        for id in &egui_full_output.textures_delta.free {
+            self.egui_renderer.free_texture(id);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if screenshot_requested {
+            self.capture_screenshot(std::path::Path::new("screenshot.png"));
+        }
+
+        Ok(())
+    }
+
+    /// Renders the scene into an off-screen texture at the current surface
+    /// size — through the same filter chain `render()` uses, so a screenshot
+    /// taken with filters enabled matches what's on screen — and writes it
+    /// out as a PNG. Doesn't touch the swapchain, so this also works for
+    /// headless rendering and test snapshots. Native-only: there's no real
+    /// filesystem to save a PNG to from a browser.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn capture_screenshot(&mut self, path: &std::path::Path) {
+        let texture_size = wgpu::Extent3d {
+            width: self.surface_config.width,
+            height: self.surface_config.height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Screenshot Texture"),
+            size: texture_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.surface_config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Screenshot Encoder"),
+            });
+
+        // `scene_color`/`multisampled_framebuffer`/`depth_texture_view` are
+        // already sized to match the surface, i.e. `texture_size`, so the
+        // scene can render straight into them instead of a one-off set of
+        // offscreen targets.
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Screenshot Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.multisampled_framebuffer,
+                    resolve_target: Some(&self.scene_color),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(clear_color(self.color_uniform)),
+                        store: wgpu::StoreOp::Discard,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            rpass.set_pipeline(&self.render_pipeline);
+            rpass.set_bind_group(
+                0,
+                self.color_uniform_pool.bind_group(),
+                &[self.color_uniform_offset],
+            );
+            rpass.draw(0..3, 0..1);
+        }
+
+        // Run the same enabled filters, in the same order, that `render()`
+        // would — so what gets saved matches what's currently on screen —
+        // then composite the result into the readback texture.
+        let input_size = (texture_size.width, texture_size.height);
+        self.run_filter_chain(&mut encoder, input_size, &texture_view);
+
+        // `bytes_per_row` in a buffer copy must be a multiple of 256, so pad
+        // each row out to the next multiple and strip the padding back out
+        // on readback.
+        let bytes_per_pixel = 4;
+        let unpadded_bytes_per_row = texture_size.width * bytes_per_pixel;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (unpadded_bytes_per_row + align - 1) / align * align;
+
+        let output_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Screenshot Output Buffer"),
+            size: (padded_bytes_per_row * texture_size.height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &output_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(texture_size.height),
+                },
+            },
+            texture_size,
+        );
+
+        self.queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = output_buffer.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            tx.send(result).unwrap();
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        rx.recv()
+            .unwrap()
+            .expect("Failed to map screenshot buffer");
+
+        let is_bgra = matches!(
+            self.surface_config.format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        );
+        let mut pixels =
+            Vec::with_capacity((unpadded_bytes_per_row * texture_size.height) as usize);
+        {
+            let padded_data = buffer_slice.get_mapped_range();
+            for row in padded_data.chunks(padded_bytes_per_row as usize) {
+                let row = &row[..unpadded_bytes_per_row as usize];
+                if is_bgra {
+                    pixels.extend(row.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]]));
+                } else {
+                    pixels.extend_from_slice(row);
+                }
             }
-        })
-        .unwrap();
+        }
+        output_buffer.unmap();
+
+        let image_buffer =
+            image::RgbaImage::from_raw(texture_size.width, texture_size.height, pixels)
+                .expect("Screenshot pixel buffer had the wrong size");
+        image_buffer.save(path).expect("Failed to save screenshot");
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -364,10 +835,19 @@ fn load_icon(path: &std::path::Path) -> winit::window::Icon {
     winit::window::Icon::from_rgba(icon_rgba, icon_width, icon_height).expect("Failed to open icon")
 }
 
-fn main() {
-    let event_loop = EventLoop::new().unwrap();
+/// Opt-in, compile-time flag: flip to `true` and rebuild to draw the wgpu
+/// surface edge-to-edge behind a transparent macOS title bar instead of
+/// leaving the default opaque bar above it. Defaults to `false` so it's
+/// genuinely opt-in rather than always-on; only takes effect on macOS,
+/// other platforms ignore it regardless of this value.
+const TRANSPARENT_TITLEBAR: bool = false;
+
+/// Builds the `Window` for the current platform. Called once up front on
+/// desktop/web, and again on Android every time the activity is resumed,
+/// since Android tears the native window down on suspend.
+fn build_window(target: &EventLoopWindowTarget<()>) -> Window {
     #[allow(unused_mut)]
-    let mut builder = winit::window::WindowBuilder::new();
+    let mut builder = WindowBuilder::new();
 
     #[cfg(target_arch = "wasm32")]
     {
@@ -383,23 +863,138 @@ fn main() {
             .unwrap();
         builder = builder.with_canvas(Some(canvas));
     }
-    #[cfg(not(target_arch = "wasm32"))]
+    #[cfg(not(any(target_arch = "wasm32", target_os = "android")))]
     {
         let icon = load_icon(std::path::Path::new("assets/icon.png"));
         builder = builder.with_window_icon(Some(icon));
     }
+    #[cfg(target_os = "macos")]
+    if TRANSPARENT_TITLEBAR {
+        use winit::platform::macos::WindowBuilderExtMacOS;
+        builder = builder
+            .with_titlebar_transparent(true)
+            .with_fullsize_content_view(true);
+    }
 
-    let window = builder.build(&event_loop).unwrap();
+    builder.build(target).unwrap()
+}
 
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-        env_logger::init();
-        pollster::block_on(run(event_loop, window));
-    }
-    #[cfg(target_arch = "wasm32")]
-    {
-        std::panic::set_hook(Box::new(console_error_panic_hook::hook));
-        console_log::init().expect("could not initialize logger");
-        wasm_bindgen_futures::spawn_local(run(event_loop, window));
-    }
+/// Holds the window and renderer `State` across their Android-driven
+/// lifecycle. `window` is `None` until the first `Resumed` event hands us a
+/// native window, and goes back to `None` on `Suspended`. `state` is built
+/// once on the first `Resumed` and then kept alive for the rest of the
+/// program; only its surface (and the window it was built from) are
+/// dropped and recreated on later `Suspended`/`Resumed` pairs.
+#[derive(Default)]
+struct App {
+    window: Option<Arc<Window>>,
+    state: Option<State>,
+}
+
+async fn run(event_loop: EventLoop<()>) {
+    let mut app = App::default();
+
+    event_loop
+        .run(move |event, target| match event {
+            Event::Resumed => {
+                let window = app
+                    .window
+                    .get_or_insert_with(|| Arc::new(build_window(target)))
+                    .clone();
+
+                match app.state.as_mut() {
+                    Some(state) => state.resume(window),
+                    None => app.state = Some(pollster::block_on(State::new(window))),
+                }
+            }
+
+            // Android destroys the native window when the app is
+            // backgrounded; drop just the surface and window built on top
+            // of it and wait for the next `Resumed` to recreate them
+            // against a fresh native window. The device, adapter and
+            // pipelines aren't tied to the native window, so they're worth
+            // keeping around rather than renegotiating from scratch.
+            Event::Suspended => {
+                if let Some(state) = app.state.as_mut() {
+                    state.suspend();
+                }
+                app.window = None;
+            }
+
+            Event::WindowEvent {
+                window_id: _,
+                event: window_event,
+            } => {
+                let Some(state) = app.state.as_mut() else {
+                    return;
+                };
+
+                if state.input(&window_event) {
+                    return;
+                }
+
+                match window_event {
+                    WindowEvent::Resized(new_size) => {
+                        state.resize(new_size);
+                        // On macos the window needs to be redrawn manually after resizing
+                        if let Some(window) = &state.window {
+                            window.request_redraw();
+                        }
+                    }
+
+                    WindowEvent::RedrawRequested => match state.render() {
+                        Ok(()) => {}
+                        // Reconfigure the surface if it's lost or outdated
+                        Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                            state.resize(state.size)
+                        }
+                        // The system is out of memory, we should probably quit
+                        Err(wgpu::SurfaceError::OutOfMemory) => target.exit(),
+                        // The frame took too long to arrive, just skip this one
+                        Err(wgpu::SurfaceError::Timeout) => log::warn!("Surface timeout"),
+                    },
+
+                    WindowEvent::CloseRequested => target.exit(),
+
+                    _ => {}
+                };
+            }
+
+            _ => {}
+        })
+        .unwrap();
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    let event_loop = EventLoop::new().unwrap();
+    env_logger::init();
+    pollster::block_on(run(event_loop));
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    let event_loop = EventLoop::new().unwrap();
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    console_log::init().expect("could not initialize logger");
+    wasm_bindgen_futures::spawn_local(run(event_loop));
+}
+
+/// Entry point used by the `android-activity` glue when building as a
+/// `cdylib` for Android; drives the same `run` loop as the native and web
+/// `main`s, with `Resumed`/`Suspended` standing in for the activity
+/// lifecycle.
+#[cfg(target_os = "android")]
+#[no_mangle]
+fn android_main(app: android_activity::AndroidApp) {
+    use winit::platform::android::EventLoopBuilderExtAndroid;
+
+    android_logger::init_once(android_logger::Config::default().with_max_level(log::LevelFilter::Info));
+
+    let event_loop = EventLoop::builder()
+        .with_android_app(app)
+        .build()
+        .unwrap();
+
+    pollster::block_on(run(event_loop));
 }
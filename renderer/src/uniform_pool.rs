@@ -0,0 +1,175 @@
+use std::marker::PhantomData;
+
+use encase::{internal::WriteInto, ShaderType, UniformBuffer};
+
+/// A growable pool of dynamically-offset uniform buffer instances, inspired
+/// by ruffle's `BufferStorage`. Every instance of `T` is packed into one
+/// `wgpu::Buffer` at a stride that respects
+/// `min_uniform_buffer_offset_alignment`, instead of handing out one buffer
+/// and bind group per instance. Draw with:
+///
+/// ```ignore
+/// let offset = pool.set(&device, &queue, index, &instance);
+/// render_pass.set_bind_group(0, pool.bind_group(), &[offset]);
+/// ```
+pub struct UniformBufferPool<T: ShaderType> {
+    label: String,
+    stride: wgpu::BufferAddress,
+    capacity: usize,
+    buffer: wgpu::Buffer,
+    // Stable for the pool's lifetime: the `render_pipeline`'s `PipelineLayout`
+    // is built from this layout once at startup, and wgpu checks bind group
+    // compatibility by layout object identity, not descriptor equality, so a
+    // `grow()` that replaced this would make every future `set_bind_group`
+    // call using the pipeline fail.
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    _marker: PhantomData<T>,
+}
+
+impl<T: ShaderType + WriteInto> UniformBufferPool<T> {
+    const INITIAL_CAPACITY: usize = 16;
+
+    pub fn new(device: &wgpu::Device, label: &str, visibility: wgpu::ShaderStages) -> Self {
+        let stride = Self::aligned_stride(device);
+        let bind_group_layout = Self::create_bind_group_layout(device, label, visibility);
+        let buffer = Self::create_buffer(device, label, stride, Self::INITIAL_CAPACITY);
+        let bind_group = Self::create_bind_group(device, label, &bind_group_layout, &buffer);
+
+        Self {
+            label: label.to_string(),
+            stride,
+            capacity: Self::INITIAL_CAPACITY,
+            buffer,
+            bind_group_layout,
+            bind_group,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Writes `instance` into the slot at `index`, growing and
+    /// reallocating the backing buffer first if `index` doesn't fit in the
+    /// current capacity. Returns the dynamic offset to pass to
+    /// `set_bind_group`.
+    pub fn set(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        index: usize,
+        instance: &T,
+    ) -> wgpu::DynamicOffset {
+        if index >= self.capacity {
+            self.grow(device, queue, index + 1);
+        }
+
+        let mut encase_buffer = UniformBuffer::new(Vec::new());
+        encase_buffer.write(instance).unwrap();
+
+        let offset = index as wgpu::BufferAddress * self.stride;
+        queue.write_buffer(&self.buffer, offset, encase_buffer.as_ref());
+
+        offset as wgpu::DynamicOffset
+    }
+
+    /// Reallocates the buffer at a larger capacity and copies the old
+    /// buffer's contents across so already-`set` instances survive the
+    /// grow. The bind group layout never changes; only the buffer and the
+    /// bind group wrapping it are rebuilt.
+    fn grow(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, min_capacity: usize) {
+        let new_capacity = min_capacity.max(self.capacity * 2);
+        let new_buffer = Self::create_buffer(device, &self.label, self.stride, new_capacity);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some(&format!("{} Grow Encoder", self.label)),
+        });
+        encoder.copy_buffer_to_buffer(
+            &self.buffer,
+            0,
+            &new_buffer,
+            0,
+            self.stride * self.capacity as wgpu::BufferAddress,
+        );
+        queue.submit(Some(encoder.finish()));
+
+        self.bind_group =
+            Self::create_bind_group(device, &self.label, &self.bind_group_layout, &new_buffer);
+        self.capacity = new_capacity;
+        self.buffer = new_buffer;
+    }
+
+    fn create_bind_group_layout(
+        device: &wgpu::Device,
+        label: &str,
+        visibility: wgpu::ShaderStages,
+    ) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&format!("{label} Bind Group Layout")),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(T::min_size().get()),
+                },
+                count: None,
+            }],
+        })
+    }
+
+    fn create_buffer(
+        device: &wgpu::Device,
+        label: &str,
+        stride: wgpu::BufferAddress,
+        capacity: usize,
+    ) -> wgpu::Buffer {
+        // `COPY_SRC` lets `grow` copy this buffer's contents into its
+        // replacement instead of silently dropping every instance written
+        // so far.
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("{label} Buffer")),
+            size: stride * capacity as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        label: &str,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{label} Bind Group")),
+            layout: bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(T::min_size().get()),
+                }),
+            }],
+        })
+    }
+
+    /// Rounds `T`'s WGSL size up to the device's minimum uniform buffer
+    /// offset alignment, so each instance's dynamic offset lands correctly.
+    fn aligned_stride(device: &wgpu::Device) -> wgpu::BufferAddress {
+        let unaligned = T::min_size().get();
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as wgpu::BufferAddress;
+
+        (unaligned + alignment - 1) / alignment * alignment
+    }
+}